@@ -0,0 +1,61 @@
+//! The parsed representation of an expression, produced by [`crate::parser`]
+//! and consumed by [`crate::evaluate`].
+
+/// A parsed dice/arithmetic expression.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Ast {
+    /// A bare integer literal.
+    Num(i32),
+    /// A dice pool, e.g. `2d6` or `a1d20r!`.
+    Dice {
+        count: i32,
+        sides: i32,
+        mods: DiceModifiers,
+    },
+    /// A binary arithmetic operation.
+    BinOp { op: BinOp, lhs: Box<Ast>, rhs: Box<Ast> },
+    /// A parenthesized sub-expression.
+    Group(Box<Ast>),
+    /// A unary negation, e.g. `-2d6`.
+    Neg(Box<Ast>),
+}
+
+/// The modifiers that can trail a dice term.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DiceModifiers {
+    pub reroll: Option<Reroll>,
+    pub explode: Option<Explode>,
+    pub advantage: Option<Advantage>,
+    pub keep: Option<Keep>,
+}
+
+/// Selects a subset of a rolled dice pool, e.g. `4d6dl1` (drop the lowest
+/// one) or `2d20kh1` (keep the highest one, i.e. advantage).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Keep {
+    Highest(u32),
+    Lowest(u32),
+    DropHighest(u32),
+    DropLowest(u32),
+}
+
+/// An arithmetic operator joining two [`Ast`] nodes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Advantage {
+    Advantage,
+    Disadvantage,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Reroll(pub i32);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Explode(pub i32);
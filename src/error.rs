@@ -1,12 +1,52 @@
-use std::num::ParseIntError;
 use std::io;
 
+use crate::span::Span;
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
-    #[error("Unable to parse expression: {0}")]
-    BadExpression(String),
-    #[error("Bad integer: {0}; {1}")]
-    BadInteger(String, ParseIntError),
+    #[error("unexpected token at {0}")]
+    UnexpectedToken(Span),
+    #[error("expected a dice count or side count at {0}")]
+    EmptyDiceCount(Span),
+    #[error("expected a closing parenthesis at {0}")]
+    ExpectedClosingParen(Span),
+    #[error("missing operand at {0}")]
+    MissingOperand(Span),
+    #[error("'{0}' is not a valid integer at {1}")]
+    NonIntegerArgument(String, Span),
+    #[error("division by zero")]
+    DivisionByZero,
+    #[error("arithmetic overflow")]
+    ArithmeticOverflow,
+    #[error("Die size must be positive, got {0}")]
+    InvalidDieSize(i32),
+    #[error("Explode threshold {0} would trigger on every roll of a {1}-sided die")]
+    ExplodeThresholdAlwaysMet(i32, i32),
     #[error(transparent)]
     IoError(#[from] io::Error),
 }
+
+impl Error {
+    /// The byte span this error points at, for callers that want to render
+    /// a caret-underlined view of the offending text.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Error::UnexpectedToken(span)
+            | Error::EmptyDiceCount(span)
+            | Error::ExpectedClosingParen(span)
+            | Error::MissingOperand(span)
+            | Error::NonIntegerArgument(_, span) => Some(*span),
+            Error::DivisionByZero
+            | Error::ArithmeticOverflow
+            | Error::InvalidDieSize(_)
+            | Error::ExplodeThresholdAlwaysMet(..)
+            | Error::IoError(_) => None,
+        }
+    }
+
+    /// Renders a caret-underlined view of the offending span against
+    /// `source`, for CLI display. Returns `None` for errors with no span.
+    pub fn highlight(&self, source: &str) -> Option<String> {
+        self.span().map(|span| span.highlight(source))
+    }
+}
@@ -0,0 +1,389 @@
+//! Turns a parsed [`Ast`] into concrete dice-roll results.
+
+use rand::Rng;
+
+use crate::ast::{Advantage, Ast, BinOp, DiceModifiers, Explode, Keep, Reroll};
+use crate::{Error, Result};
+
+/// Upper bound on how many times a single die may explode, so a threshold
+/// that (almost) always triggers can't spin the evaluator forever.
+const MAX_EXPLOSIONS: usize = 100;
+
+/// The outcome of evaluating an [`Ast`]: the final total plus a breakdown of
+/// every die rolled for it, across the whole expression. Dice dropped by a
+/// keep/drop modifier are listed too, though they are not counted in `total`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RollResult {
+    pub total: i32,
+    pub dice: Vec<DieRoll>,
+}
+
+/// A single die's contribution to a [`RollResult`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DieRoll {
+    /// The number of sides on the die that was rolled.
+    pub sides: i32,
+    /// The value of this die after rerolls/explosions; a die dropped by a
+    /// keep/drop modifier keeps its value here but is left out of the total.
+    pub kept: i32,
+    /// Every individual roll made for this die, in the order rolled,
+    /// including values that were rerolled away.
+    pub rolls: Vec<i32>,
+}
+
+/// Governs how long a `Reroll` modifier keeps rerolling a qualifying die.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum RerollPolicy {
+    /// Reroll a qualifying die exactly once, keeping whatever comes up second.
+    #[default]
+    Once,
+    /// Keep rerolling until the die clears the threshold, stopping after
+    /// `MAX_EXPLOSIONS` (100) rerolls even if it never does.
+    UntilAboveThreshold,
+}
+
+/// Evaluates `ast`, rerolling at most once per die.
+pub fn evaluate(ast: &Ast, rng: &mut impl Rng) -> Result<RollResult> {
+    evaluate_with_policy(ast, rng, RerollPolicy::default())
+}
+
+/// Evaluates `ast`, using `reroll_policy` to decide how persistent the
+/// `Reroll` modifier is.
+pub fn evaluate_with_policy(
+    ast: &Ast,
+    rng: &mut impl Rng,
+    reroll_policy: RerollPolicy,
+) -> Result<RollResult> {
+    match ast {
+        Ast::Num(n) => Ok(RollResult {
+            total: *n,
+            dice: Vec::new(),
+        }),
+        Ast::Group(inner) => evaluate_with_policy(inner, rng, reroll_policy),
+        Ast::Neg(inner) => {
+            let mut result = evaluate_with_policy(inner, rng, reroll_policy)?;
+            result.total = -result.total;
+            Ok(result)
+        }
+        Ast::BinOp { op, lhs, rhs } => {
+            let mut lhs = evaluate_with_policy(lhs, rng, reroll_policy)?;
+            let rhs = evaluate_with_policy(rhs, rng, reroll_policy)?;
+            lhs.total = apply_op(*op, lhs.total, rhs.total)?;
+            lhs.dice.extend(rhs.dice);
+            Ok(lhs)
+        }
+        Ast::Dice { count, sides, mods } => roll_dice(*count, *sides, mods, rng, reroll_policy),
+    }
+}
+
+fn apply_op(op: BinOp, lhs: i32, rhs: i32) -> Result<i32> {
+    match op {
+        BinOp::Add => lhs.checked_add(rhs).ok_or(Error::ArithmeticOverflow),
+        BinOp::Sub => lhs.checked_sub(rhs).ok_or(Error::ArithmeticOverflow),
+        BinOp::Mul => lhs.checked_mul(rhs).ok_or(Error::ArithmeticOverflow),
+        BinOp::Div if rhs == 0 => Err(Error::DivisionByZero),
+        BinOp::Div => lhs.checked_div(rhs).ok_or(Error::ArithmeticOverflow),
+    }
+}
+
+fn roll_dice(
+    count: i32,
+    sides: i32,
+    mods: &DiceModifiers,
+    rng: &mut impl Rng,
+    policy: RerollPolicy,
+) -> Result<RollResult> {
+    validate(sides, mods)?;
+
+    let dice = match mods.advantage {
+        Some(advantage) => {
+            let a = roll_pool(count, sides, mods, rng, policy)?;
+            let b = roll_pool(count, sides, mods, rng, policy)?;
+            pick_pool(advantage, a, b)
+        }
+        None => roll_pool(count, sides, mods, rng, policy)?,
+    };
+
+    let total = match mods.keep {
+        Some(keep) => kept_total(&dice, keep)?,
+        None => checked_sum(dice.iter().map(|d| d.kept))?,
+    };
+
+    Ok(RollResult { total, dice })
+}
+
+/// Sums kept values, failing instead of wrapping on overflow.
+fn checked_sum(values: impl Iterator<Item = i32>) -> Result<i32> {
+    values.try_fold(0, |acc, v| acc.checked_add(v).ok_or(Error::ArithmeticOverflow))
+}
+
+/// Sorts the pool by kept value and sums only the dice `keep` selects.
+fn kept_total(dice: &[DieRoll], keep: Keep) -> Result<i32> {
+    let mut order: Vec<usize> = (0..dice.len()).collect();
+    order.sort_by_key(|&i| dice[i].kept);
+    let n = order.len();
+
+    let selected = match keep {
+        Keep::Highest(k) => &order[n.saturating_sub(k as usize)..],
+        Keep::Lowest(k) => &order[..(k as usize).min(n)],
+        Keep::DropHighest(k) => &order[..n.saturating_sub(k as usize)],
+        Keep::DropLowest(k) => &order[(k as usize).min(n)..],
+    };
+
+    checked_sum(selected.iter().map(|&i| dice[i].kept))
+}
+
+fn validate(sides: i32, mods: &DiceModifiers) -> Result<()> {
+    if sides <= 0 {
+        return Err(Error::InvalidDieSize(sides));
+    }
+
+    if let Some(Explode(threshold)) = mods.explode {
+        // A d4 can still roll a 1, so any threshold at or below that is
+        // guaranteed to trigger on every single roll.
+        if threshold <= 1 {
+            return Err(Error::ExplodeThresholdAlwaysMet(threshold, sides));
+        }
+    }
+
+    Ok(())
+}
+
+fn pick_pool(advantage: Advantage, a: Vec<DieRoll>, b: Vec<DieRoll>) -> Vec<DieRoll> {
+    let a_total: i32 = a.iter().map(|d| d.kept).sum();
+    let b_total: i32 = b.iter().map(|d| d.kept).sum();
+
+    match advantage {
+        Advantage::Advantage if a_total >= b_total => a,
+        Advantage::Advantage => b,
+        Advantage::Disadvantage if a_total <= b_total => a,
+        Advantage::Disadvantage => b,
+    }
+}
+
+fn roll_pool(
+    count: i32,
+    sides: i32,
+    mods: &DiceModifiers,
+    rng: &mut impl Rng,
+    policy: RerollPolicy,
+) -> Result<Vec<DieRoll>> {
+    (0..count).map(|_| roll_die(sides, mods, rng, policy)).collect()
+}
+
+fn roll_die(
+    sides: i32,
+    mods: &DiceModifiers,
+    rng: &mut impl Rng,
+    policy: RerollPolicy,
+) -> Result<DieRoll> {
+    let mut rolls = vec![roll_once(sides, rng)];
+
+    if let Some(Reroll(threshold)) = mods.reroll {
+        match policy {
+            RerollPolicy::Once => {
+                if *rolls.last().unwrap() <= threshold {
+                    rolls.push(roll_once(sides, rng));
+                }
+            }
+            RerollPolicy::UntilAboveThreshold => {
+                while *rolls.last().unwrap() <= threshold && rolls.len() <= MAX_EXPLOSIONS {
+                    rolls.push(roll_once(sides, rng));
+                }
+            }
+        }
+    }
+
+    let mut kept = *rolls.last().unwrap();
+
+    if let Some(Explode(threshold)) = mods.explode {
+        let mut last = kept;
+        let mut depth = 0;
+        while last >= threshold && depth < MAX_EXPLOSIONS {
+            let extra = roll_once(sides, rng);
+            rolls.push(extra);
+            kept = kept.checked_add(extra).ok_or(Error::ArithmeticOverflow)?;
+            last = extra;
+            depth += 1;
+        }
+    }
+
+    Ok(DieRoll {
+        sides,
+        kept,
+        rolls,
+    })
+}
+
+fn roll_once(sides: i32, rng: &mut impl Rng) -> i32 {
+    rng.gen_range(1..=sides)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::mock::StepRng;
+
+    use super::*;
+
+    fn dice(count: i32, sides: i32, mods: DiceModifiers) -> Ast {
+        Ast::Dice { count, sides, mods }
+    }
+
+    #[test]
+    fn basic_roll_stays_within_bounds() {
+        let mut rng = StepRng::new(7, 11);
+        let result = evaluate(&dice(3, 6, DiceModifiers::default()), &mut rng).unwrap();
+
+        assert_eq!(3, result.dice.len());
+        for die in &result.dice {
+            assert!(die.kept >= 1 && die.kept <= 6);
+        }
+        assert_eq!(result.total, result.dice.iter().map(|d| d.kept).sum::<i32>());
+    }
+
+    #[test]
+    fn arithmetic_and_grouping_are_evaluated() {
+        let mut rng = StepRng::new(0, 1);
+        let ast = crate::parse("(2d6+3)*2").unwrap();
+        let result = evaluate(&ast, &mut rng).unwrap();
+
+        assert_eq!((result.dice.iter().map(|d| d.kept).sum::<i32>() + 3) * 2, result.total);
+    }
+
+    #[test]
+    fn division_truncates_toward_zero() {
+        let mut rng = StepRng::new(0, 1);
+        let ast = crate::parse("7/2").unwrap();
+        let result = evaluate(&ast, &mut rng).unwrap();
+
+        assert_eq!(3, result.total);
+    }
+
+    #[test]
+    fn division_by_zero_is_an_error() {
+        let mut rng = StepRng::new(0, 1);
+        let ast = crate::parse("1/0").unwrap();
+        let err = evaluate(&ast, &mut rng).unwrap_err();
+
+        assert!(matches!(err, Error::DivisionByZero));
+    }
+
+    #[test]
+    fn arithmetic_overflow_is_an_error() {
+        let mut rng = StepRng::new(0, 1);
+        let ast = crate::parse("2000000000*3").unwrap();
+        let err = evaluate(&ast, &mut rng).unwrap_err();
+
+        assert!(matches!(err, Error::ArithmeticOverflow));
+    }
+
+    #[test]
+    fn reroll_replaces_a_low_roll_once() {
+        let mut rng = StepRng::new(0, u64::MAX / 6);
+        let mods = DiceModifiers {
+            reroll: Some(Reroll(1)),
+            ..Default::default()
+        };
+        let result = evaluate(&dice(1, 6, mods), &mut rng).unwrap();
+
+        assert_eq!(2, result.dice[0].rolls.len());
+        assert_eq!(result.dice[0].kept, *result.dice[0].rolls.last().unwrap());
+    }
+
+    #[test]
+    fn explode_keeps_adding_dice_while_threshold_is_met() {
+        // A generator stuck on one value near u32::MAX falls in `gen_range`'s
+        // rejection zone and spins forever. This seed instead sits squarely
+        // in the accepted zone for `1..=6` and maps to face 6; stepping by 1
+        // keeps every one of the ~100 rolls in that same zone, so each
+        // explosion resolves on its first draw.
+        let mut rng = StepRng::new(3_579_140_414, 1);
+        let mods = DiceModifiers {
+            explode: Some(Explode(6)),
+            ..Default::default()
+        };
+        let result = evaluate(&dice(1, 6, mods), &mut rng).unwrap();
+
+        assert_eq!(MAX_EXPLOSIONS + 1, result.dice[0].rolls.len());
+    }
+
+    #[test]
+    fn exploding_dice_report_overflow_instead_of_wrapping() {
+        // This generator always yields u32::MAX raw, which this range
+        // accepts on the first draw, so every roll lands on the max face
+        // (2_000_000_000) and the second one overflows i32 once added.
+        let mut rng = StepRng::new(u64::from(u32::MAX), 0);
+        let mods = DiceModifiers {
+            explode: Some(Explode(2)),
+            ..Default::default()
+        };
+        let err = evaluate(&dice(1, 2_000_000_000, mods), &mut rng).unwrap_err();
+
+        assert!(matches!(err, Error::ArithmeticOverflow));
+    }
+
+    #[test]
+    fn drop_lowest_excludes_the_smallest_roll() {
+        let dice: Vec<_> = [4, 1, 3, 6]
+            .into_iter()
+            .map(|kept| DieRoll {
+                sides: 6,
+                kept,
+                rolls: vec![kept],
+            })
+            .collect();
+
+        assert_eq!(13, kept_total(&dice, Keep::DropLowest(1)).unwrap());
+    }
+
+    #[test]
+    fn keep_highest_selects_only_the_top_n() {
+        let dice: Vec<_> = [4, 1, 3, 6]
+            .into_iter()
+            .map(|kept| DieRoll {
+                sides: 6,
+                kept,
+                rolls: vec![kept],
+            })
+            .collect();
+
+        assert_eq!(10, kept_total(&dice, Keep::Highest(2)).unwrap());
+    }
+
+    #[test]
+    fn advantage_keeps_the_higher_pool() {
+        let low = vec![DieRoll {
+            sides: 20,
+            kept: 3,
+            rolls: vec![3],
+        }];
+        let high = vec![DieRoll {
+            sides: 20,
+            kept: 18,
+            rolls: vec![18],
+        }];
+
+        assert_eq!(high, pick_pool(Advantage::Advantage, low.clone(), high.clone()));
+        assert_eq!(low, pick_pool(Advantage::Disadvantage, low.clone(), high.clone()));
+    }
+
+    #[test]
+    fn zero_sided_die_is_rejected() {
+        let mut rng = StepRng::new(0, 1);
+        let err = evaluate(&dice(1, 0, DiceModifiers::default()), &mut rng).unwrap_err();
+
+        assert!(matches!(err, Error::InvalidDieSize(0)));
+    }
+
+    #[test]
+    fn explode_threshold_of_one_is_rejected() {
+        let mut rng = StepRng::new(0, 1);
+        let mods = DiceModifiers {
+            explode: Some(Explode(1)),
+            ..Default::default()
+        };
+        let err = evaluate(&dice(1, 6, mods), &mut rng).unwrap_err();
+
+        assert!(matches!(err, Error::ExplodeThresholdAlwaysMet(1, 6)));
+    }
+}
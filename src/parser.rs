@@ -1,130 +1,526 @@
-use crate::{Error, Expression, ExpressionPair, Result};
-
-pub(crate) fn parse(s: &str) -> Result<Vec<Expression>> {
-    let mut state = State::Bounded { idx: 0 };
-    let mut compound_expression = Vec::new();
-    let mut expression = Expression::default();
-
-    for (current_idx, u) in s.bytes().enumerate() {
-        match u.to_ascii_lowercase() {
-            // Advantage/disadvantage signifier. Either of these is an emitting boundary token.
-            u @ b'a' | u @ b's' => {
-                expression.apply_state(s, &state, current_idx)?;
-                if !expression.is_empty() {
-                    compound_expression.push(expression);
-                }
+//! A recursive-descent (Pratt) parser for dice expressions.
+//!
+//! A primary is a number, a parenthesized sub-expression, or a dice term
+//! (optionally prefixed with an advantage/disadvantage marker and trailing
+//! reroll/explode/keep-drop modifiers, e.g. `4d6dl1` or `2d20kh1`). `+`/`-`
+//! bind looser than `*`/`/`, and parentheses reset precedence back to the
+//! loosest level, so expressions like `(2d6+3)*2 + s1d20` parse the way a
+//! reader would expect.
+//!
+//! A run of digits is only treated as a dice term when it's spelled with an
+//! explicit `d`/`D` separator, an advantage/disadvantage prefix, a trailing
+//! `r`/`!` modifier, or a trailing `kh`/`kl`/`dh`/`dl` keep-drop suffix; a
+//! bare number is just an integer.
 
-                expression = Expression::default();
-                expression.advantage = u == b'a';
-                expression.disadvantage = u == b's';
-                state = State::Base {
-                    idx: current_idx + 1,
-                };
-            }
+use crate::ast::{Advantage, Ast, BinOp, DiceModifiers, Explode, Keep, Reroll};
+use crate::span::Span;
+use crate::{Error, Result};
 
-            b'+' | b'-' => {
-                expression.apply_state(s, &state, current_idx)?;
-                if !expression.is_empty() {
-                    compound_expression.push(expression);
-                }
+pub(crate) fn parse(input: &str) -> Result<Ast> {
+    let tokens = tokenize(input)?;
+    let mut parser = Parser { tokens, pos: 0 };
+    let ast = parser.parse_expr(0)?;
 
-                expression = Expression::default();
-                expression.invert = u == b'-';
-                state = State::Base {
-                    idx: current_idx + 1,
-                };
-            }
+    if let Some((_, span)) = parser.tokens.get(parser.pos) {
+        return Err(Error::UnexpectedToken(*span));
+    }
 
-            b'd' => {
-                state = state.into_bounded();
-            }
+    Ok(ast)
+}
 
-            b'r' => {
-                expression.apply_state(s, &state, current_idx)?;
-                state = State::Reroll {
-                    idx: current_idx + 1,
-                };
-            }
+struct Parser {
+    tokens: Vec<(Token, Span)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn parse_expr(&mut self, min_bp: u8) -> Result<Ast> {
+        let mut lhs = self.parse_primary()?;
 
-            b'!' => {
-                expression.apply_state(s, &state, current_idx)?;
-                state = State::Bang {
-                    idx: current_idx + 1,
-                };
+        while let Some(op) = self.peek_op() {
+            let (left_bp, right_bp) = binding_power(op);
+            if left_bp < min_bp {
+                break;
             }
 
-            _ => (),
+            self.pos += 1;
+            let rhs = self.parse_expr(right_bp)?;
+            lhs = Ast::BinOp {
+                op,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            };
         }
+
+        Ok(lhs)
     }
 
-    expression.apply_state(s, &state, s.len())?;
-    compound_expression.push(expression);
-    Ok(compound_expression)
-}
+    fn parse_primary(&mut self) -> Result<Ast> {
+        let end_of_input = Span::at(self.tokens.last().map_or(0, |(_, span)| span.end));
 
-/// Parses a base dice expression, e.g. 2d6
-pub(crate) fn parse_expression(expr: &str) -> Result<ExpressionPair> {
-    let mut parts = dbg!(expr).split(|c| c == 'd' || c == 'D');
-    let left = parts.next().ok_or_else(|| Error::bad_expression(expr))?;
-    let right = parts.next();
+        match self.advance() {
+            Some((Token::Num(n), _)) => Ok(Ast::Num(n)),
+            Some((
+                Token::Dice {
+                    advantage,
+                    count,
+                    sides,
+                    reroll,
+                    explode,
+                    keep,
+                },
+                _,
+            )) => Ok(Ast::Dice {
+                count,
+                sides,
+                mods: DiceModifiers {
+                    reroll,
+                    explode,
+                    advantage,
+                    keep,
+                },
+            }),
+            Some((Token::Minus, _)) => {
+                Ok(Ast::Neg(Box::new(self.parse_expr(UNARY_BINDING_POWER)?)))
+            }
+            Some((Token::LParen, span)) => {
+                let inner = self.parse_expr(0)?;
+                match self.advance() {
+                    Some((Token::RParen, _)) => Ok(Ast::Group(Box::new(inner))),
+                    Some((_, span)) => Err(Error::ExpectedClosingParen(span)),
+                    None => Err(Error::ExpectedClosingParen(span)),
+                }
+            }
+            Some((_, span)) => Err(Error::UnexpectedToken(span)),
+            None => Err(Error::MissingOperand(end_of_input)),
+        }
+    }
 
-    // Expressions must only contain a maximum of two parts at this level.
-    if parts.next().is_some() {
-        return Err(Error::bad_expression(expr));
+    fn peek_op(&self) -> Option<BinOp> {
+        match &self.tokens.get(self.pos)?.0 {
+            Token::Plus => Some(BinOp::Add),
+            Token::Minus => Some(BinOp::Sub),
+            Token::Star => Some(BinOp::Mul),
+            Token::Slash => Some(BinOp::Div),
+            _ => None,
+        }
     }
 
-    match right {
-        Some(right) => Ok(ExpressionPair {
-            count: left.parse().map_err(|e| Error::parse_integer(e, expr))?,
-            value: right.parse().map_err(|e| Error::parse_integer(e, expr))?,
-        }),
-        None => Ok(ExpressionPair {
-            count: 1,
-            value: left.parse().map_err(|e| Error::parse_integer(e, expr))?,
-        }),
+    fn advance(&mut self) -> Option<(Token, Span)> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+}
+
+/// Binding powers for `+ - * /`, low-to-high then left-to-right.
+fn binding_power(op: BinOp) -> (u8, u8) {
+    match op {
+        BinOp::Add | BinOp::Sub => (1, 2),
+        BinOp::Mul | BinOp::Div => (3, 4),
     }
 }
 
-#[derive(Clone, Debug)]
-pub(crate) enum State {
-    /// Found non-emitting boundary token bang (!) at idx.
-    Bang { idx: usize },
+const UNARY_BINDING_POWER: u8 = 5;
 
-    /// Has encountered no control characters since the previous boundary token.
-    Base { idx: usize },
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Num(i32),
+    Dice {
+        advantage: Option<Advantage>,
+        count: i32,
+        sides: i32,
+        reroll: Option<Reroll>,
+        explode: Option<Explode>,
+        keep: Option<Keep>,
+    },
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    LParen,
+    RParen,
+}
+
+fn tokenize(input: &str) -> Result<Vec<(Token, Span)>> {
+    let bytes = input.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
 
-    /// Found control character dice (d).
-    Bounded { idx: usize },
+    while i < bytes.len() {
+        match bytes[i] {
+            b' ' | b'\t' => i += 1,
+            b'+' => {
+                tokens.push((Token::Plus, Span::at(i)));
+                i += 1;
+            }
+            b'-' => {
+                tokens.push((Token::Minus, Span::at(i)));
+                i += 1;
+            }
+            b'*' => {
+                tokens.push((Token::Star, Span::at(i)));
+                i += 1;
+            }
+            b'/' => {
+                tokens.push((Token::Slash, Span::at(i)));
+                i += 1;
+            }
+            b'(' => {
+                tokens.push((Token::LParen, Span::at(i)));
+                i += 1;
+            }
+            b')' => {
+                tokens.push((Token::RParen, Span::at(i)));
+                i += 1;
+            }
+            b'a' | b'A' | b's' | b'S' | b'0'..=b'9' => {
+                let (token, span, next) = lex_term(input, i)?;
+                tokens.push((token, span));
+                i = next;
+            }
+            _ => return Err(Error::UnexpectedToken(Span::at(i))),
+        }
+    }
 
-    /// Found non-emitting boundary token reroll (r) at idx.
-    Reroll { idx: usize },
+    Ok(tokens)
 }
 
-impl State {
-    pub(crate) fn idx(&self) -> usize {
-        match self {
-            State::Bang { idx }
-            | State::Base { idx }
-            | State::Bounded { idx }
-            | State::Reroll { idx } => *idx,
+/// Lexes a single term starting at `start`: an optional advantage prefix, a
+/// count and/or side count, any trailing reroll/explode modifiers, and a
+/// trailing keep/drop modifier.
+/// Falls back to a plain [`Token::Num`] when none of that decoration shows up.
+fn lex_term(input: &str, start: usize) -> Result<(Token, Span, usize)> {
+    let bytes = input.as_bytes();
+    let mut i = start;
+
+    let advantage = match bytes[i].to_ascii_lowercase() {
+        b'a' => {
+            i += 1;
+            Some(Advantage::Advantage)
+        }
+        b's' => {
+            i += 1;
+            Some(Advantage::Disadvantage)
+        }
+        _ => None,
+    };
+
+    let first = read_digits(input, &mut i)?;
+
+    // A `d` only introduces a count/sides separator when it's followed by
+    // digits; `dh`/`dl` are the drop-highest/drop-lowest keep modifiers, and
+    // must not be greedily consumed as that separator (e.g. "20dh1" is
+    // 1d20 dropping the highest, not an empty dice count before an `h`).
+    let (count, sides) = if bytes.get(i).map(u8::to_ascii_lowercase) == Some(b'd')
+        && bytes.get(i + 1).is_some_and(u8::is_ascii_digit)
+    {
+        i += 1;
+        (Some(first), read_digits(input, &mut i)?)
+    } else {
+        (None, first)
+    };
+
+    let reroll = if bytes.get(i) == Some(&b'r') {
+        i += 1;
+        Some(Reroll(read_digits_opt(input, &mut i).unwrap_or(1)))
+    } else {
+        None
+    };
+
+    let explode = if bytes.get(i) == Some(&b'!') {
+        i += 1;
+        Some(Explode(read_digits_opt(input, &mut i).unwrap_or(sides)))
+    } else {
+        None
+    };
+
+    let keep = match (
+        bytes.get(i).map(u8::to_ascii_lowercase),
+        bytes.get(i + 1).map(u8::to_ascii_lowercase),
+    ) {
+        (Some(b'k'), Some(b'h')) => {
+            i += 2;
+            Some(Keep::Highest(read_count(input, &mut i)))
+        }
+        (Some(b'k'), Some(b'l')) => {
+            i += 2;
+            Some(Keep::Lowest(read_count(input, &mut i)))
+        }
+        (Some(b'd'), Some(b'h')) => {
+            i += 2;
+            Some(Keep::DropHighest(read_count(input, &mut i)))
+        }
+        (Some(b'd'), Some(b'l')) => {
+            i += 2;
+            Some(Keep::DropLowest(read_count(input, &mut i)))
         }
+        _ => None,
+    };
+
+    let span = Span {
+        start,
+        end: i,
+    };
+
+    if advantage.is_none() && count.is_none() && reroll.is_none() && explode.is_none() && keep.is_none() {
+        return Ok((Token::Num(sides), span, i));
     }
 
-    fn into_bounded(self) -> Self {
-        State::Bounded { idx: self.idx() }
+    Ok((
+        Token::Dice {
+            advantage,
+            count: count.unwrap_or(1),
+            sides,
+            reroll,
+            explode,
+            keep,
+        },
+        span,
+        i,
+    ))
+}
+
+fn read_digits(input: &str, i: &mut usize) -> Result<i32> {
+    let start = *i;
+    let bytes = input.as_bytes();
+    while bytes.get(*i).is_some_and(u8::is_ascii_digit) {
+        *i += 1;
     }
+
+    if *i == start {
+        return Err(Error::EmptyDiceCount(Span::at(start)));
+    }
+
+    input[start..*i]
+        .parse()
+        .map_err(|_| Error::NonIntegerArgument(input[start..*i].into(), Span { start, end: *i }))
 }
 
-impl Default for State {
-    fn default() -> Self {
-        State::Bounded { idx: 0 }
+/// Reads an optional non-negative count trailing a keep/drop modifier,
+/// defaulting to 1 (e.g. the `1` in `kh1` is implicit in `kh`).
+fn read_count(input: &str, i: &mut usize) -> u32 {
+    read_digits_opt(input, i).unwrap_or(1).max(0) as u32
+}
+
+fn read_digits_opt(input: &str, i: &mut usize) -> Option<i32> {
+    let start = *i;
+    let bytes = input.as_bytes();
+    while bytes.get(*i).is_some_and(u8::is_ascii_digit) {
+        *i += 1;
+    }
+
+    if *i == start {
+        None
+    } else {
+        input[start..*i].parse().ok()
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::ast::{Advantage, Ast, BinOp, DiceModifiers, Explode, Keep, Reroll};
+
+    fn dice(count: i32, sides: i32) -> Ast {
+        Ast::Dice {
+            count,
+            sides,
+            mods: DiceModifiers::default(),
+        }
+    }
+
+    #[test]
+    fn bare_dice_expression() {
+        assert_eq!(dice(2, 6), parse("2d6").unwrap());
+    }
+
+    #[test]
+    fn bare_number_is_an_integer_not_a_die() {
+        assert_eq!(Ast::Num(20), parse("20").unwrap());
+    }
+
+    #[test]
+    fn reroll_and_explode_modifiers() {
+        let actual = parse("2d6r2!5").unwrap();
+        let expected = Ast::Dice {
+            count: 2,
+            sides: 6,
+            mods: DiceModifiers {
+                reroll: Some(Reroll(2)),
+                explode: Some(Explode(5)),
+                advantage: None,
+                keep: None,
+            },
+        };
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn advantage_and_disadvantage_prefixes() {
+        let expected = Ast::Dice {
+            count: 1,
+            sides: 20,
+            mods: DiceModifiers {
+                advantage: Some(Advantage::Advantage),
+                ..Default::default()
+            },
+        };
+
+        assert_eq!(expected, parse("a20").unwrap());
+        assert_eq!(expected, parse("a1d20").unwrap());
+    }
+
+    #[test]
+    fn arithmetic_respects_precedence() {
+        // 2 + 3 * 4 should parse as 2 + (3 * 4), not (2 + 3) * 4.
+        let actual = parse("2+3*4").unwrap();
+        let expected = Ast::BinOp {
+            op: BinOp::Add,
+            lhs: Box::new(Ast::Num(2)),
+            rhs: Box::new(Ast::BinOp {
+                op: BinOp::Mul,
+                lhs: Box::new(Ast::Num(3)),
+                rhs: Box::new(Ast::Num(4)),
+            }),
+        };
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn parentheses_override_precedence() {
+        let actual = parse("(2+3)*4").unwrap();
+        let expected = Ast::BinOp {
+            op: BinOp::Mul,
+            lhs: Box::new(Ast::Group(Box::new(Ast::BinOp {
+                op: BinOp::Add,
+                lhs: Box::new(Ast::Num(2)),
+                rhs: Box::new(Ast::Num(3)),
+            }))),
+            rhs: Box::new(Ast::Num(4)),
+        };
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn compound_dice_and_arithmetic_expression() {
+        let actual = parse("(2d6+3)*2 + s1d20").unwrap();
+        let expected = Ast::BinOp {
+            op: BinOp::Add,
+            lhs: Box::new(Ast::BinOp {
+                op: BinOp::Mul,
+                lhs: Box::new(Ast::Group(Box::new(Ast::BinOp {
+                    op: BinOp::Add,
+                    lhs: Box::new(dice(2, 6)),
+                    rhs: Box::new(Ast::Num(3)),
+                }))),
+                rhs: Box::new(Ast::Num(2)),
+            }),
+            rhs: Box::new(Ast::Dice {
+                count: 1,
+                sides: 20,
+                mods: DiceModifiers {
+                    advantage: Some(Advantage::Disadvantage),
+                    ..Default::default()
+                },
+            }),
+        };
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn unary_negation() {
+        let actual = parse("-2d6").unwrap();
+        assert_eq!(Ast::Neg(Box::new(dice(2, 6))), actual);
+    }
+
+    #[test]
+    fn unmatched_parenthesis_is_an_error() {
+        assert!(parse("(2d6+3").is_err());
+    }
+
+    #[test]
+    fn drop_lowest_modifier() {
+        let actual = parse("4d6dl1").unwrap();
+        let expected = Ast::Dice {
+            count: 4,
+            sides: 6,
+            mods: DiceModifiers {
+                keep: Some(Keep::DropLowest(1)),
+                ..Default::default()
+            },
+        };
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn keep_highest_modifier() {
+        let actual = parse("2d20kh1").unwrap();
+        let expected = Ast::Dice {
+            count: 2,
+            sides: 20,
+            mods: DiceModifiers {
+                keep: Some(Keep::Highest(1)),
+                ..Default::default()
+            },
+        };
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn drop_highest_without_a_leading_dice_separator() {
+        // "20dh1" has no explicit `d` count/sides separator, so the `d` of
+        // `dh1` must not be mistaken for one.
+        let actual = parse("20dh1").unwrap();
+        let expected = Ast::Dice {
+            count: 1,
+            sides: 20,
+            mods: DiceModifiers {
+                keep: Some(Keep::DropHighest(1)),
+                ..Default::default()
+            },
+        };
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn keep_lowest_defaults_count_to_one() {
+        let actual = parse("3d8kl").unwrap();
+        let expected = Ast::Dice {
+            count: 3,
+            sides: 8,
+            mods: DiceModifiers {
+                keep: Some(Keep::Lowest(1)),
+                ..Default::default()
+            },
+        };
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn unmatched_parenthesis_reports_expected_closing_paren() {
+        let err = parse("(2d6+3").unwrap_err();
+        assert!(matches!(err, crate::Error::ExpectedClosingParen(_)));
+    }
+
+    #[test]
+    fn trailing_operator_reports_missing_operand() {
+        let err = parse("2d6+").unwrap_err();
+        assert!(matches!(err, crate::Error::MissingOperand(_)));
+    }
+
     #[test]
-    fn it_works() {
-        dbg!(super::parse("a20+10+s2d10r2!7-3").unwrap());
+    fn overflowing_literal_reports_non_integer_argument() {
+        let err = parse("99999999999999999999").unwrap_err();
+        assert!(matches!(err, crate::Error::NonIntegerArgument(_, _)));
+        assert_eq!(Some(Span { start: 0, end: 20 }), err.span());
     }
 }
@@ -0,0 +1,37 @@
+//! Source positions used to point at the offending text in a parse error.
+
+use std::fmt;
+
+/// A half-open byte range into the original input.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    /// A single-byte span starting at `pos`.
+    pub fn at(pos: usize) -> Self {
+        Span {
+            start: pos,
+            end: pos + 1,
+        }
+    }
+
+    /// Renders a two-line, caret-underlined view of this span against
+    /// `source`, suitable for printing to a terminal.
+    pub fn highlight(&self, source: &str) -> String {
+        let marker_len = self.end.saturating_sub(self.start).max(1);
+        format!(
+            "{source}\n{}{}",
+            " ".repeat(self.start),
+            "^".repeat(marker_len)
+        )
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}